@@ -0,0 +1,195 @@
+//! Pluggable variant rules (diagonals, anti-knight/anti-king adjacency,
+//! killer cages, ...) layered on top of the plain row/column/box rules.
+
+use crate::bitmanip::BitManip;
+use crate::grid::SudokuGrid;
+
+/// A variant rule that can forbid digits at a tile, and optionally enforce
+/// a whole-grid invariant once the board is full (e.g. a cage's sum).
+pub trait Constraint {
+    /// returns a mask of digits ruled out for `tileno` by this constraint
+    fn forbidden(&self, grid: &SudokuGrid, tileno: usize) -> u32;
+
+    /// returns whether this constraint holds on a fully-filled `grid`
+    fn satisfied(&self, _grid: &SudokuGrid) -> bool {
+        true
+    }
+
+    /// clones this constraint into a fresh boxed trait object
+    fn clone_box(&self) -> Box<dyn Constraint>;
+}
+
+impl Clone for Box<dyn Constraint> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// X-Sudoku: both main diagonals must also contain each digit exactly once
+#[derive(Clone)]
+pub struct Diagonals;
+
+impl Constraint for Diagonals {
+    fn forbidden(&self, grid: &SudokuGrid, tileno: usize) -> u32 {
+        let row_width = *grid.row_width();
+        let row = tileno / row_width;
+        let col = tileno % row_width;
+        let mut bad = 0;
+
+        if row == col {
+            for i in 0..row_width {
+                bad.set_bit(grid[(i, i)]);
+            }
+        }
+
+        if row + col == row_width - 1 {
+            for i in 0..row_width {
+                bad.set_bit(grid[(i, row_width - 1 - i)]);
+            }
+        }
+
+        bad
+    }
+
+    fn clone_box(&self) -> Box<dyn Constraint> {
+        Box::new(self.clone())
+    }
+}
+
+/// no two cells a knight's move apart may share a digit
+#[derive(Clone)]
+pub struct AntiKnight;
+
+const KNIGHT_MOVES: [(isize, isize); 8] = [
+    (-2, -1),
+    (-2, 1),
+    (-1, -2),
+    (-1, 2),
+    (1, -2),
+    (1, 2),
+    (2, -1),
+    (2, 1),
+];
+
+impl Constraint for AntiKnight {
+    fn forbidden(&self, grid: &SudokuGrid, tileno: usize) -> u32 {
+        adjacency_forbidden(grid, tileno, &KNIGHT_MOVES)
+    }
+
+    fn clone_box(&self) -> Box<dyn Constraint> {
+        Box::new(self.clone())
+    }
+}
+
+/// no two orthogonally or diagonally adjacent cells may share a digit
+#[derive(Clone)]
+pub struct AntiKing;
+
+const KING_MOVES: [(isize, isize); 8] = [
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+];
+
+impl Constraint for AntiKing {
+    fn forbidden(&self, grid: &SudokuGrid, tileno: usize) -> u32 {
+        adjacency_forbidden(grid, tileno, &KING_MOVES)
+    }
+
+    fn clone_box(&self) -> Box<dyn Constraint> {
+        Box::new(self.clone())
+    }
+}
+
+/// forbids the digits already present at any in-bounds `(row + dr, col + dc)`
+/// offset from `tileno`
+fn adjacency_forbidden(grid: &SudokuGrid, tileno: usize, offsets: &[(isize, isize)]) -> u32 {
+    let row_width = *grid.row_width();
+    let row = (tileno / row_width) as isize;
+    let col = (tileno % row_width) as isize;
+    let mut bad = 0;
+
+    for &(dr, dc) in offsets {
+        let r = row + dr;
+        let c = col + dc;
+
+        if r >= 0 && c >= 0 && (r as usize) < row_width && (c as usize) < row_width {
+            bad.set_bit(grid[(r as usize, c as usize)]);
+        }
+    }
+
+    bad
+}
+
+/// a killer cage: the listed tiles may not repeat a digit and must sum to
+/// exactly `sum`
+#[derive(Clone)]
+pub struct Cage {
+    pub sum: u32,
+    pub tiles: Vec<usize>,
+}
+
+impl Cage {
+    pub fn new(sum: u32, tiles: Vec<usize>) -> Self {
+        Self { sum, tiles }
+    }
+}
+
+impl Constraint for Cage {
+    fn forbidden(&self, grid: &SudokuGrid, tileno: usize) -> u32 {
+        if !self.tiles.contains(&tileno) {
+            return 0;
+        }
+
+        let mut bad = 0;
+        let mut filled_sum = 0;
+        let mut empty_others = 0;
+
+        for &t in &self.tiles {
+            if t == tileno {
+                continue;
+            }
+
+            let v = grid[t];
+            if v == 0 {
+                empty_others += 1;
+            } else {
+                bad.set_bit(v);
+                filled_sum += v;
+            }
+        }
+
+        let remaining = self.sum.saturating_sub(filled_sum);
+
+        if empty_others == 0 {
+            // this is the last empty tile in the cage: only `remaining` fits
+            for digit in 1..=*grid.row_width() as u32 {
+                if digit != remaining {
+                    bad.set_bit(digit);
+                }
+            }
+        } else {
+            // the other empty tiles need at least 1 each, so this tile can't
+            // take more than what they'd leave behind
+            let max_for_tile = remaining.saturating_sub(empty_others);
+            for digit in (max_for_tile + 1)..=*grid.row_width() as u32 {
+                bad.set_bit(digit);
+            }
+        }
+
+        bad
+    }
+
+    fn satisfied(&self, grid: &SudokuGrid) -> bool {
+        self.tiles.iter().map(|&t| grid[t]).sum::<u32>() == self.sum
+    }
+
+    fn clone_box(&self) -> Box<dyn Constraint> {
+        Box::new(self.clone())
+    }
+}
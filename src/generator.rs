@@ -0,0 +1,113 @@
+//! Puzzle generation: fills a random full solution, then digs holes out of
+//! it while keeping the remaining puzzle uniquely solvable.
+
+use std::convert::TryFrom;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+use crate::bitmanip::BitManip;
+use crate::grid::SudokuGrid;
+
+/// how aggressively `generate` digs holes out of the full solution
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+    Extreme,
+}
+
+impl Difficulty {
+    /// target number of clues left behind on a board with `cells` tiles
+    fn clue_count(&self, cells: usize) -> usize {
+        let fraction = match self {
+            Difficulty::Easy => 0.55,
+            Difficulty::Medium => 0.45,
+            Difficulty::Hard => 0.35,
+            Difficulty::Extreme => 0.25,
+        };
+
+        ((cells as f64) * fraction) as usize
+    }
+}
+
+impl SudokuGrid {
+    /// generates a playable puzzle with a guaranteed unique solution: fills
+    /// an empty board using a shuffled backtracking search, then removes
+    /// clues in rotationally-paired twos (the centre tile of an odd-width
+    /// board alone), keeping each removal only if the puzzle stays uniquely
+    /// solvable, until the difficulty's clue target is reached or every tile
+    /// has been tried
+    pub fn generate(cell_width: usize, difficulty: Difficulty, seed: u64) -> SudokuGrid {
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let row_width = cell_width * cell_width;
+        let mut grid = SudokuGrid::try_from(vec![0u32; row_width * row_width]).unwrap();
+        fill(&mut grid, &mut rng);
+
+        let target_clues = difficulty.clue_count(grid.tiles().len());
+
+        let mut digging_order: Vec<usize> = (0..grid.tiles().len()).collect();
+        digging_order.shuffle(&mut rng);
+
+        let mut clues_left = grid.tiles().len();
+        for tileno in digging_order {
+            if clues_left <= target_clues {
+                break;
+            }
+
+            // dug as someone else's rotationally-paired tile already
+            if grid[tileno] == 0 {
+                continue;
+            }
+
+            // dig the 180-degree rotational partner alongside `tileno` so the
+            // finished puzzle keeps point symmetry; the centre tile of an
+            // odd-width board pairs with itself
+            let pair = grid.tiles().len() - 1 - tileno;
+
+            let previous_tile = grid[tileno];
+            let previous_pair = grid[pair];
+
+            grid[tileno] = 0;
+            grid[pair] = 0;
+
+            if crate::is_unique(&grid) {
+                clues_left -= if pair == tileno { 1 } else { 2 };
+            } else {
+                grid[tileno] = previous_tile;
+                grid[pair] = previous_pair;
+            }
+        }
+
+        grid
+    }
+}
+
+/// fills every empty tile of `grid` with a valid full solution, trying each
+/// cell's candidate digits in a shuffled order so repeated calls with
+/// different RNG state produce different solutions
+fn fill(grid: &mut SudokuGrid, rng: &mut StdRng) -> bool {
+    let tileno = match grid.iter().position(|&t| t == 0) {
+        Some(tileno) => tileno,
+        None => return true,
+    };
+
+    let tries = crate::possible(grid, tileno);
+    let mut candidates: Vec<u32> = (1..=*grid.row_width() as u32)
+        .filter(|digit| tries.test_bit(*digit))
+        .collect();
+    candidates.shuffle(rng);
+
+    for digit in candidates {
+        grid[tileno] = digit;
+        if fill(grid, rng) {
+            return true;
+        }
+    }
+
+    grid[tileno] = 0;
+
+    false
+}
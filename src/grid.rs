@@ -1,25 +1,74 @@
 use std::convert::TryFrom;
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::iter;
 use std::ops;
 use std::slice;
 use std::str::FromStr;
 
+use crate::constraint::Constraint;
+
 /// Structure representing a sudoku grid
 /// empty tiles are represented by 0
-#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+#[derive(Clone)]
 pub struct SudokuGrid {
     tiles: Vec<u32>,
-    cell_width: usize,
+    /// number of rows per box
+    box_rows: usize,
+    /// number of columns per box
+    box_cols: usize,
     row_width: usize,
+    /// extra variant rules (diagonals, anti-knight, killer cages, ...)
+    /// layered on top of the row/column/box rules
+    constraints: Vec<Box<dyn Constraint>>,
+}
+
+impl fmt::Debug for SudokuGrid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SudokuGrid")
+            .field("tiles", &self.tiles)
+            .field("box_rows", &self.box_rows)
+            .field("box_cols", &self.box_cols)
+            .field("row_width", &self.row_width)
+            .field("constraints", &self.constraints.len())
+            .finish()
+    }
+}
+
+// constraints aren't comparable/hashable, so equality and hashing are
+// defined over the board state alone
+impl PartialEq for SudokuGrid {
+    fn eq(&self, other: &Self) -> bool {
+        self.tiles == other.tiles
+            && self.box_rows == other.box_rows
+            && self.box_cols == other.box_cols
+            && self.row_width == other.row_width
+    }
+}
+
+impl Eq for SudokuGrid {}
+
+impl Hash for SudokuGrid {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.tiles.hash(state);
+        self.box_rows.hash(state);
+        self.box_cols.hash(state);
+        self.row_width.hash(state);
+    }
 }
 
 #[allow(dead_code)]
 impl SudokuGrid {
-    /// get the cell width member from the struct
+    /// get the number of rows per box
     #[inline]
-    pub fn cell_width(&self) -> &usize {
-        &self.cell_width
+    pub fn box_rows(&self) -> &usize {
+        &self.box_rows
+    }
+
+    /// get the number of columns per box
+    #[inline]
+    pub fn box_cols(&self) -> &usize {
+        &self.box_cols
     }
 
     /// get the cell width member from the struct
@@ -40,6 +89,18 @@ impl SudokuGrid {
         self.tiles.iter()
     }
 
+    /// registers an extra variant constraint (diagonals, anti-knight,
+    /// killer cages, ...) on top of the row/column/box rules
+    pub fn add_constraint(&mut self, constraint: Box<dyn Constraint>) {
+        self.constraints.push(constraint);
+    }
+
+    /// returns the registered variant constraints
+    #[inline]
+    pub fn constraints(&self) -> &[Box<dyn Constraint>] {
+        &self.constraints
+    }
+
     /// Returns an interator over row `row`
     pub fn iter_row(&self, row: usize) -> slice::Iter<u32> {
         if row < self.row_width {
@@ -65,7 +126,7 @@ impl SudokuGrid {
         }
     }
 
-    /// Returns an interator over the cell which `col`, `row` is in
+    /// Returns an interator over the box which `col`, `row` is in
     pub fn iter_cell(&self, row: usize, col: usize) -> CellIter {
         if col >= self.row_width {
             panic!(
@@ -79,51 +140,63 @@ impl SudokuGrid {
             )
         }
 
-        // calculate the number of chunsk to skip
-        let chunks_to_skip = (row / self.cell_width) * self.row_width + col / self.cell_width;
-
         CellIter {
-            inner: self
-                .tiles
-                .chunks(self.cell_width)
-                .skip(chunks_to_skip)
-                .step_by(self.cell_width)
-                .take(self.cell_width)
-                .flatten(),
+            grid: self,
+            box_row_start: (row / self.box_rows) * self.box_rows,
+            box_col_start: (col / self.box_cols) * self.box_cols,
+            idx: 0,
         }
     }
 }
 
+/// Iterates over the tiles sharing a box with the tile `iter_cell` was
+/// called with, walking a `box_rows` x `box_cols` block of the grid
 pub struct CellIter<'a> {
-    inner: iter::Flatten<iter::Take<iter::StepBy<iter::Skip<slice::Chunks<'a, u32>>>>>,
+    grid: &'a SudokuGrid,
+    box_row_start: usize,
+    box_col_start: usize,
+    idx: usize,
 }
 
 impl<'a> Iterator for CellIter<'a> {
     type Item = &'a u32;
 
-    #[inline]
     fn next(&mut self) -> Option<Self::Item> {
-        self.inner.next()
+        let total = self.grid.box_rows * self.grid.box_cols;
+        if self.idx >= total {
+            return None;
+        }
+
+        let dr = self.idx / self.grid.box_cols;
+        let dc = self.idx % self.grid.box_cols;
+        self.idx += 1;
+
+        Some(&self.grid[(self.box_row_start + dr, self.box_col_start + dc)])
     }
 
-    #[inline]
     fn size_hint(&self) -> (usize, Option<usize>) {
-        self.inner.size_hint()
+        let total = self.grid.box_rows * self.grid.box_cols;
+        let remaining = total - self.idx;
+        (remaining, Some(remaining))
     }
 }
 
 impl fmt::Display for SudokuGrid {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let bottom_cell_border_centre = vec!["═"; self.cell_width].join("═╧═");
-        let top_cell_border_centre = vec!["═"; self.cell_width].join("═╤═");
+        // boxes are `box_cols` wide, so there are `row_width / box_cols` of
+        // them across a row
+        let box_groups_across = self.row_width / self.box_cols;
+
+        let bottom_cell_border_centre = vec!["═"; self.box_cols].join("═╧═");
+        let top_cell_border_centre = vec!["═"; self.box_cols].join("═╤═");
 
         let bottom_border_centre = iter::repeat(bottom_cell_border_centre)
-            .take(self.cell_width)
+            .take(box_groups_across)
             .collect::<Vec<_>>()
             .join("═╩═");
 
         let top_border_centre = iter::repeat(top_cell_border_centre)
-            .take(self.cell_width)
+            .take(box_groups_across)
             .collect::<Vec<_>>()
             .join("═╦═");
 
@@ -132,8 +205,8 @@ impl fmt::Display for SudokuGrid {
 
         let row_sep = vec![
             "╟─",
-            &iter::repeat(vec!["─"; self.cell_width].join("─┼─"))
-                .take(self.cell_width)
+            &iter::repeat(vec!["─"; self.box_cols].join("─┼─"))
+                .take(box_groups_across)
                 .collect::<Vec<_>>()
                 .join("─╫─"),
             "─╢",
@@ -142,8 +215,8 @@ impl fmt::Display for SudokuGrid {
 
         let cell_row_sep = vec![
             "╠═",
-            &iter::repeat(vec!["═"; self.cell_width].join("═╪═"))
-                .take(self.cell_width)
+            &iter::repeat(vec!["═"; self.box_cols].join("═╪═"))
+                .take(box_groups_across)
                 .collect::<Vec<_>>()
                 .join("═╬═"),
             "═╣",
@@ -161,7 +234,7 @@ impl fmt::Display for SudokuGrid {
                     write!(f, "{: ^3}", cell)?;
                 }
 
-                if x % self.cell_width == self.cell_width - 1 {
+                if x % self.box_cols == self.box_cols - 1 {
                     write!(f, "║")?;
                 } else {
                     write!(f, "│")?;
@@ -171,7 +244,7 @@ impl fmt::Display for SudokuGrid {
 
             // write row seperator
             if y != self.row_width - 1 {
-                if y % self.cell_width == self.cell_width - 1 {
+                if y % self.box_rows == self.box_rows - 1 {
                     writeln!(f, "{}", cell_row_sep)?;
                 } else {
                     writeln!(f, "{}", row_sep)?;
@@ -185,8 +258,8 @@ impl fmt::Display for SudokuGrid {
 /// Custom error type to represent the ways that parsing a sudoku from a CSV can fail
 #[derive(Debug, Fail)]
 pub enum SudokuParseError {
-    #[fail(display = "board not square")]
-    NonSquare,
+    #[fail(display = "box dimensions do not produce a valid board")]
+    InvalidDimensions,
     #[fail(display = "digit not in range for board")]
     DigitOutOfRange,
     #[fail(display = "invalid digit in board")]
@@ -197,9 +270,28 @@ impl FromStr for SudokuGrid {
     type Err = SudokuParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // an explicit "box_rows,box_cols" header (e.g. "3,3" for a classic
+        // 9x9 board, "2,3" for a 6x6 board with 2x3 boxes) lets non-square
+        // boxes round-trip; without one, square boxes are inferred from the
+        // tile count like before
+        let mut lines = s.lines();
+        let first = lines.next().unwrap_or("");
+        let header: Option<(usize, usize)> = first
+            .split(',')
+            .map(str::parse::<usize>)
+            .collect::<Result<Vec<_>, _>>()
+            .ok()
+            .filter(|dims| dims.len() == 2)
+            .map(|dims| (dims[0], dims[1]));
+
+        let data = match header {
+            Some(_) => lines.collect::<Vec<_>>().join("\n"),
+            None => s.to_string(),
+        };
+
         // parse the tiles
         // propagating any internal errors into the outer result type
-        let tiles_fromstr: Result<Vec<u32>, SudokuParseError> = s
+        let tiles_fromstr: Result<Vec<u32>, SudokuParseError> = data
             .lines()
             .flat_map(|line| {
                 line.split(',').map(|n| {
@@ -215,20 +307,28 @@ impl FromStr for SudokuGrid {
         // check if any of the parses failed with ParseIntError
         let tiles = tiles_fromstr?;
 
-        // calculate the cell width
-        let cell_width = (tiles.len() as f64).powf(0.25) as usize;
-        let row_width = cell_width.pow(2);
+        let (box_rows, box_cols) = match header {
+            Some(dims) => dims,
+            None => {
+                let cell_width = (tiles.len() as f64).powf(0.25) as usize;
+                (cell_width, cell_width)
+            }
+        };
+
+        let row_width = box_rows * box_cols;
 
         // validate the parsed data
-        if cell_width.pow(4) != tiles.len() {
-            Err(SudokuParseError::NonSquare)
+        if box_rows == 0 || box_cols == 0 || row_width * row_width != tiles.len() {
+            Err(SudokuParseError::InvalidDimensions)
         } else if !tiles.iter().all(|&n| n <= row_width as u32) {
             Err(SudokuParseError::DigitOutOfRange)
         } else {
             Ok(Self {
                 tiles,
-                cell_width,
+                box_rows,
+                box_cols,
                 row_width,
+                constraints: Vec::new(),
             })
         }
     }
@@ -286,19 +386,22 @@ impl TryFrom<Vec<u32>> for SudokuGrid {
     type Error = SudokuParseError;
 
     fn try_from(tiles: Vec<u32>) -> Result<Self, Self::Error> {
-        // calculate the cell width
+        // infers square boxes from the tile count; use `FromStr` with an
+        // explicit header for rectangular boxes
         let cell_width = (tiles.len() as f64).powf(0.25) as usize;
         let row_width = cell_width.pow(2);
 
         if cell_width.pow(4) != tiles.len() {
-            Err(SudokuParseError::NonSquare)
+            Err(SudokuParseError::InvalidDimensions)
         } else if !tiles.iter().all(|&n| n <= row_width as u32) {
             Err(SudokuParseError::DigitOutOfRange)
         } else {
             Ok(Self {
                 tiles,
-                cell_width,
+                box_rows: cell_width,
+                box_cols: cell_width,
                 row_width,
+                constraints: Vec::new(),
             })
         }
     }
@@ -10,22 +10,70 @@ use grid::SudokuGrid;
 mod bitmanip;
 use bitmanip::BitManip;
 
+mod constraint;
+use constraint::{AntiKing, AntiKnight, Cage, Diagonals};
+
+mod sat;
+
+mod generator;
+use generator::Difficulty;
+
 #[allow(unused_imports)]
 use std::convert::TryInto;
 use std::env;
 use std::fs;
 
 fn main() -> std::io::Result<()> {
-    let mut sudoku: SudokuGrid = if let Some(path) = env::args().nth(1) {
-        fs::read_to_string(path)?.parse()
+    let args: Vec<String> = env::args().skip(1).collect();
+    let use_sat = args.iter().any(|arg| arg == "--sat");
+    let use_diagonal = args.iter().any(|arg| arg == "--diagonal");
+    let use_anti_knight = args.iter().any(|arg| arg == "--anti-knight");
+    let use_anti_king = args.iter().any(|arg| arg == "--anti-king");
+
+    let cages: Vec<Cage> = args
+        .iter()
+        .filter_map(|arg| arg.strip_prefix("--cage="))
+        .map(parse_cage)
+        .collect();
+
+    let generate = args
+        .iter()
+        .find_map(|arg| arg.strip_prefix("--generate="))
+        .map(parse_generate);
+
+    let path = args.iter().find(|arg| !arg.starts_with("--")).cloned();
+
+    let mut sudoku: SudokuGrid = if let Some((cell_width, difficulty, seed)) = generate {
+        SudokuGrid::generate(cell_width, difficulty, seed)
+    } else if let Some(path) = path {
+        fs::read_to_string(path)?.parse().unwrap()
     } else {
-        include_str!("inputs/extreme.csv").parse()
+        include_str!("inputs/extreme.csv").parse().unwrap()
+    };
+
+    if use_diagonal {
+        sudoku.add_constraint(Box::new(Diagonals));
+    }
+    if use_anti_knight {
+        sudoku.add_constraint(Box::new(AntiKnight));
+    }
+    if use_anti_king {
+        sudoku.add_constraint(Box::new(AntiKing));
+    }
+    for cage in cages {
+        sudoku.add_constraint(Box::new(cage));
     }
-    .unwrap();
 
     println!("{}", sudoku);
 
-    let result = solve(&mut sudoku);
+    // the SAT backend only encodes the plain row/column/box rules, so it
+    // can't honour variant constraints yet; fall back to the
+    // constraint-aware backtracking solver rather than silently ignoring them
+    let result = if use_sat && sudoku.constraints().is_empty() {
+        sat::solve_sat(&mut sudoku)
+    } else {
+        solve(&mut sudoku)
+    };
     println!("{:?}", result);
     if result.is_solved() {
         println!("{}", sudoku);
@@ -34,43 +82,207 @@ fn main() -> std::io::Result<()> {
     Ok(())
 }
 
-/// wrapper to set up the recursive function
+/// parses a `--cage=` argument of the form `SUM:TILE,TILE,...`, e.g.
+/// `--cage=15:0,1,9` for a cage summing to 15 over tiles 0, 1 and 9
+fn parse_cage(spec: &str) -> Cage {
+    let (sum, tiles) = spec
+        .split_once(':')
+        .expect("cage spec must be of the form SUM:TILE,TILE,...");
+
+    let sum: u32 = sum.parse().expect("cage sum must be a number");
+    let tiles: Vec<usize> = tiles
+        .split(',')
+        .map(|tile| tile.parse().expect("cage tile index must be a number"))
+        .collect();
+
+    Cage::new(sum, tiles)
+}
+
+/// parses a `--generate=` argument of the form `CELL_WIDTH,DIFFICULTY,SEED`,
+/// e.g. `--generate=3,hard,42` for a 9x9 board at hard difficulty
+fn parse_generate(spec: &str) -> (usize, Difficulty, u64) {
+    let parts: Vec<&str> = spec.split(',').collect();
+    if parts.len() != 3 {
+        panic!("generate spec must be of the form CELL_WIDTH,DIFFICULTY,SEED");
+    }
+
+    let cell_width: usize = parts[0].parse().expect("cell width must be a number");
+    let difficulty = parse_difficulty(parts[1]);
+    let seed: u64 = parts[2].parse().expect("seed must be a number");
+
+    (cell_width, difficulty, seed)
+}
+
+/// parses a difficulty name (`easy`, `medium`, `hard` or `extreme`)
+fn parse_difficulty(s: &str) -> Difficulty {
+    match s {
+        "easy" => Difficulty::Easy,
+        "medium" => Difficulty::Medium,
+        "hard" => Difficulty::Hard,
+        "extreme" => Difficulty::Extreme,
+        _ => panic!("difficulty must be one of easy, medium, hard, extreme"),
+    }
+}
+
+/// wrapper to set up the candidate masks and kick off the recursive solve
 fn solve(sudoku: &mut SudokuGrid) -> SolveState {
-    // find first empty tile
-    if let Some(&first_zero) = sudoku.iter().find(|tile| **tile == 0) {
-        solve_rec(sudoku, first_zero as usize)
-    } else {
-        SolveState::Solved
+    let mut candidates = init_candidates(sudoku);
+    solve_rec(sudoku, &mut candidates)
+}
+
+/// builds the initial per-tile candidate masks from the givens
+fn init_candidates(sudoku: &SudokuGrid) -> Vec<u32> {
+    let digit_mask = digit_mask(sudoku);
+
+    (0..sudoku.tiles().len())
+        .map(|tileno| {
+            if sudoku[tileno] == 0 {
+                possible(sudoku, tileno) & digit_mask
+            } else {
+                0
+            }
+        })
+        .collect()
+}
+
+/// mask with bits `1..=row_width` set, used to strip the bits above
+/// `row_width` that `possible()` leaves set (it returns the complement of
+/// the ruled-out digits, so every out-of-range bit comes back set)
+fn digit_mask(sudoku: &SudokuGrid) -> u32 {
+    let mut mask = 0;
+    for digit in 1..=*sudoku.row_width() as u32 {
+        mask.set_bit(digit);
     }
+    mask
 }
 
-/// does the recursive backtracking to solve the sudoku
-fn solve_rec(sudoku: &mut SudokuGrid, tileno: usize) -> SolveState {
-    let tries = possible(sudoku, tileno);
+/// does the recursive constraint-propagation solve: repeatedly assigns any
+/// tile with exactly one remaining candidate (naked singles), then branches
+/// on the tile with the fewest candidates (minimum-remaining-values) when
+/// propagation stalls
+fn solve_rec(sudoku: &mut SudokuGrid, candidates: &mut [u32]) -> SolveState {
+    // tiles assigned by propagation in this call, so they can be undone on backtrack
+    let mut assigned = Vec::new();
 
-    let next_tile = sudoku
-        .iter()
-        .skip(tileno + 1)
-        .position(|tile| *tile == 0)
-        .map(|pos| pos + tileno + 1);
-
-    for num in (1..=*sudoku.row_width() as u32).filter(|digit| tries.test_bit(*digit)) {
-        sudoku[tileno] = num;
-        if let Some(nextno) = next_tile {
-            let state = solve_rec(sudoku, nextno as usize);
-            if state.is_solved() {
-                return SolveState::Solved;
+    loop {
+        let mut progress = false;
+
+        for tileno in 0..sudoku.tiles().len() {
+            if sudoku[tileno] != 0 {
+                continue;
+            }
+
+            let mask = candidates[tileno];
+            if mask == 0 {
+                // contradiction: an empty tile with no legal digits left
+                undo(sudoku, &assigned);
+                return SolveState::UnSolved;
+            }
+
+            if mask.count_ones() == 1 {
+                assign(sudoku, candidates, tileno, mask.trailing_zeros());
+                assigned.push(tileno);
+                progress = true;
             }
-        } else {
+        }
+
+        if !progress {
+            break;
+        }
+    }
+
+    let branch_tile = (0..sudoku.tiles().len())
+        .filter(|&tileno| sudoku[tileno] == 0)
+        .min_by_key(|&tileno| candidates[tileno].count_ones());
+
+    let tileno = match branch_tile {
+        Some(tileno) => tileno,
+        None => {
+            return if sudoku.constraints().iter().all(|c| c.satisfied(sudoku)) {
+                SolveState::Solved
+            } else {
+                undo(sudoku, &assigned);
+                SolveState::UnSolved
+            };
+        }
+    };
+
+    let mut tries = candidates[tileno];
+    while tries != 0 {
+        let digit = tries.trailing_zeros();
+        tries.clear_bit(digit);
+
+        let mut branch_candidates = candidates.to_vec();
+        assign(sudoku, &mut branch_candidates, tileno, digit);
+
+        if solve_rec(sudoku, &mut branch_candidates).is_solved() {
+            candidates.copy_from_slice(&branch_candidates);
             return SolveState::Solved;
         }
+
+        sudoku[tileno] = 0;
     }
 
-    sudoku[tileno] = 0;
+    undo(sudoku, &assigned);
 
     SolveState::UnSolved
 }
 
+/// resets every tile in `assigned` back to empty, undoing propagation
+/// assignments made earlier in this call before backtracking out of it
+fn undo(sudoku: &mut SudokuGrid, assigned: &[usize]) {
+    for &tileno in assigned {
+        sudoku[tileno] = 0;
+    }
+}
+
+/// assigns `digit` to `tileno` and clears it from the candidate masks of
+/// every peer sharing a row, column or box with it
+fn assign(sudoku: &mut SudokuGrid, candidates: &mut [u32], tileno: usize, digit: u32) {
+    sudoku[tileno] = digit;
+    candidates[tileno] = 0;
+
+    for peer in peers(sudoku, tileno) {
+        candidates[peer].clear_bit(digit);
+    }
+
+    // variant constraints (diagonals, anti-knight, cages, ...) can relate
+    // tiles that aren't row/column/box peers, so re-tighten every open tile
+    // against them; skipped entirely on boards with no constraints so plain
+    // boards keep the cheap peer-only update above
+    if !sudoku.constraints().is_empty() {
+        for other in 0..candidates.len() {
+            if sudoku[other] == 0 {
+                for constraint in sudoku.constraints() {
+                    candidates[other] &= !constraint.forbidden(sudoku, other);
+                }
+            }
+        }
+    }
+}
+
+/// returns the tile indices sharing a row, column or box with `tileno`
+fn peers(sudoku: &SudokuGrid, tileno: usize) -> impl Iterator<Item = usize> + '_ {
+    let row_width = *sudoku.row_width();
+    let box_rows = *sudoku.box_rows();
+    let box_cols = *sudoku.box_cols();
+    let row = tileno / row_width;
+    let col = tileno % row_width;
+
+    (0..sudoku.tiles().len()).filter(move |&other| {
+        if other == tileno {
+            return false;
+        }
+
+        let other_row = other / row_width;
+        let other_col = other % row_width;
+
+        other_row == row
+            || other_col == col
+            || (other_row / box_rows == row / box_rows && other_col / box_cols == col / box_cols)
+    })
+}
+
 // returns a which digits are possible at this tile number
 // each are bitpacked into a u32
 // bit 1 being set means 1 is possible
@@ -92,9 +304,60 @@ fn possible(sudoku: &SudokuGrid, tileno: usize) -> u32 {
         bad.set_bit(*n);
     }
 
+    for constraint in sudoku.constraints() {
+        bad |= constraint.forbidden(sudoku, tileno);
+    }
+
     return !bad;
 }
 
+/// exhaustively explores the search tree counting complete solutions,
+/// short-circuiting once `cap` have been found (`cap = 2` cheaply answers
+/// "is this puzzle unique?" without exploring the whole tree)
+pub fn count_solutions(sudoku: &SudokuGrid, cap: usize) -> usize {
+    let mut sudoku = sudoku.clone();
+    let mut found = 0;
+    count_solutions_rec(&mut sudoku, cap, &mut found);
+    found
+}
+
+/// plain backtracking (branches on the first empty tile, candidates from
+/// `possible()`, no naked-single propagation or MRV as in `solve_rec`) that
+/// counts every full assignment reached instead of stopping at the first one
+fn count_solutions_rec(sudoku: &mut SudokuGrid, cap: usize, found: &mut usize) {
+    if *found >= cap {
+        return;
+    }
+
+    let tileno = match sudoku.iter().position(|&tile| tile == 0) {
+        Some(tileno) => tileno,
+        None => {
+            if sudoku.constraints().iter().all(|c| c.satisfied(sudoku)) {
+                *found += 1;
+            }
+            return;
+        }
+    };
+
+    let tries = possible(sudoku, tileno);
+    for digit in (1..=*sudoku.row_width() as u32).filter(|digit| tries.test_bit(*digit)) {
+        sudoku[tileno] = digit;
+        count_solutions_rec(sudoku, cap, found);
+
+        if *found >= cap {
+            break;
+        }
+    }
+
+    sudoku[tileno] = 0;
+}
+
+/// convenience wrapper over `count_solutions`: does this puzzle have
+/// exactly one solution?
+pub fn is_unique(sudoku: &SudokuGrid) -> bool {
+    count_solutions(sudoku, 2) == 1
+}
+
 #[derive(Debug)]
 enum SolveState {
     Solved,
@@ -0,0 +1,125 @@
+//! A SAT-encoding solver backend.
+//!
+//! The backtracking solver in `main.rs` is impractical on large boards
+//! (16x16, 25x25 and up). This module encodes a `SudokuGrid` as CNF and
+//! hands it to a SAT solver, which scales far better on those sizes.
+
+use crate::grid::SudokuGrid;
+use crate::SolveState;
+
+use varisat::{CnfFormula, ExtendFormula, Lit, Solver};
+
+/// maps a (row, col, digit) triple to its DIMACS variable, one boolean per
+/// (cell, digit) pair: `var(row, col, digit) = (row * N + col) * N + (digit - 1) + 1`
+fn var(row_width: usize, row: usize, col: usize, digit: u32) -> Lit {
+    let dimacs = ((row * row_width + col) * row_width + (digit as usize - 1) + 1) as isize;
+    Lit::from_dimacs(dimacs)
+}
+
+/// emits pairwise at-most-one clauses over `lits`: for every pair, at least
+/// one of the two must be false
+fn at_most_one(formula: &mut CnfFormula, lits: &[Lit]) {
+    for i in 0..lits.len() {
+        for j in (i + 1)..lits.len() {
+            formula.add_clause(&[!lits[i], !lits[j]]);
+        }
+    }
+}
+
+/// builds the CNF encoding of `sudoku`: each cell has exactly one digit, and
+/// each digit appears exactly once per row, column and box
+///
+/// the "exactly one" groups are encoded with both halves spelled out (an
+/// at-least-one clause plus pairwise at-most-one clauses) rather than
+/// relying on cell-exactly-one plus at-least-one-per-group to force
+/// uniqueness by pigeonhole: that weaker encoding is logically equivalent
+/// but leaves CDCL nothing to unit-propagate on, and is pathologically slow
+/// on boards much bigger than 9x9
+fn encode(sudoku: &SudokuGrid) -> CnfFormula {
+    let row_width = *sudoku.row_width();
+    let box_rows = *sudoku.box_rows();
+    let box_cols = *sudoku.box_cols();
+    let mut formula = CnfFormula::new();
+
+    // each cell has at least one digit, and at most one
+    for row in 0..row_width {
+        for col in 0..row_width {
+            let lits: Vec<Lit> = (1..=row_width as u32)
+                .map(|digit| var(row_width, row, col, digit))
+                .collect();
+            formula.add_clause(&lits);
+            at_most_one(&mut formula, &lits);
+        }
+    }
+
+    // each digit appears at least once, and at most once, per row, per
+    // column and per box
+    for digit in 1..=row_width as u32 {
+        for row in 0..row_width {
+            let lits: Vec<Lit> = (0..row_width)
+                .map(|col| var(row_width, row, col, digit))
+                .collect();
+            formula.add_clause(&lits);
+            at_most_one(&mut formula, &lits);
+        }
+
+        for col in 0..row_width {
+            let lits: Vec<Lit> = (0..row_width)
+                .map(|row| var(row_width, row, col, digit))
+                .collect();
+            formula.add_clause(&lits);
+            at_most_one(&mut formula, &lits);
+        }
+
+        for box_row in (0..row_width).step_by(box_rows) {
+            for box_col in (0..row_width).step_by(box_cols) {
+                let lits: Vec<Lit> = (0..box_rows)
+                    .flat_map(|dr| (0..box_cols).map(move |dc| (dr, dc)))
+                    .map(|(dr, dc)| var(row_width, box_row + dr, box_col + dc, digit))
+                    .collect();
+                formula.add_clause(&lits);
+                at_most_one(&mut formula, &lits);
+            }
+        }
+    }
+
+    // fix the givens
+    for row in 0..row_width {
+        for col in 0..row_width {
+            let digit = sudoku[(row, col)];
+            if digit != 0 {
+                formula.add_clause(&[var(row_width, row, col, digit)]);
+            }
+        }
+    }
+
+    formula
+}
+
+/// solves `sudoku` by encoding it as CNF and handing it to a SAT solver,
+/// decoding the satisfying assignment back into `tiles` on success
+pub fn solve_sat(sudoku: &mut SudokuGrid) -> SolveState {
+    let row_width = *sudoku.row_width();
+    let formula = encode(sudoku);
+
+    let mut solver = Solver::new();
+    solver.add_formula(&formula);
+
+    match solver.solve() {
+        Ok(true) => {
+            for lit in solver.model().unwrap() {
+                if !lit.is_positive() {
+                    continue;
+                }
+
+                let index = lit.var().index();
+                let tileno = index / row_width;
+                let digit = (index % row_width) as u32 + 1;
+                sudoku[tileno] = digit;
+            }
+
+            SolveState::Solved
+        }
+        _ => SolveState::UnSolved,
+    }
+}